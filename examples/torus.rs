@@ -15,16 +15,13 @@
 #[macro_use]
 extern crate glium;
 extern crate cgmath;
-extern crate num;
 extern crate isosurface;
 
 use glium::glutin;
 use glium::Surface;
 use glium::index::PrimitiveType;
 use glutin::{GlProfile, GlRequest, Api, Event, WindowEvent, ControlFlow};
-use cgmath::{Vector3, vec3, Matrix4, Point3};
-use num::range_step;
-use std::slice;
+use cgmath::{vec3, Matrix4, Point3};
 use isosurface::marching_cubes;
 
 #[derive(Copy, Clone)]
@@ -36,16 +33,6 @@ struct Vertex {
 
 implement_vertex!(Vertex, position, normal);
 
-/// This is used to reinterpret slices of floats as slices of repr(C) structs, without any
-/// copying. It is optimal, but it is also punching holes in the type system. I hope that Rust
-/// provides safe functionality to handle this in the future. In the meantime, reproduce
-/// this workaround at your own risk.
-fn reinterpret_cast_slice<S, T>(input : &[S], length : usize) -> &[T] {
-    unsafe {
-        slice::from_raw_parts(input.as_ptr() as *const T, length)
-    }
-}
-
 /// The distance-field equation for a torus
 fn torus(x : f32, y : f32, z : f32) -> f32 {
     const R1 : f32 = 1.0 / 4.0;
@@ -63,27 +50,6 @@ impl marching_cubes::Source for Torus {
     }
 }
 
-/// Takes an array of vertices, and indices defining the faces of a triangle mesh.
-/// Outputs a welded array of vertices + normals matching the indices.
-fn build_smooth_normals(vertices : &[Vector3<f32>], indices : &[u32], output : &mut Vec<Vector3<f32>>) {
-    for &v in vertices.iter() {
-        output.push(v);
-        output.push(vec3(0.0, 0.0, 0.0));
-    }
-
-    for i in range_step(0, indices.len(), 3) {
-        let v0 : Vector3<f32> = vertices[indices[i] as usize];
-        let v1 : Vector3<f32> = vertices[indices[i+1] as usize];
-        let v2 : Vector3<f32> = vertices[indices[i+2] as usize];
-
-        let n = (v1 - v0).cross(v2 - v0);
-
-        for j in 0..3 {
-            output[(indices[i+j]*2 + 1) as usize] = output[(indices[i+j]*2 + 1) as usize] + n;
-        }
-    }
-}
-
 fn main() {
     let mut events_loop = glutin::EventsLoop::new();
     let window = glutin::WindowBuilder::new()
@@ -103,18 +69,15 @@ fn main() {
     let mut indices = vec![];
     let mut marching_cubes = marching_cubes::MarchingCubes::new(256);
 
-    marching_cubes.extract(&torus, &mut vertices, &mut indices);
-
-    let mut vertices_with_normals = vec![];
+    marching_cubes.extract_with_normals(&torus, &mut vertices, &mut indices);
 
-    build_smooth_normals(reinterpret_cast_slice(&vertices, vertices.len()/3), &indices, &mut vertices_with_normals);
+    let vertices : Vec<Vertex> = vertices.iter().map(|v| Vertex {
+        position: v.position,
+        normal: v.normal,
+    }).collect();
 
-    let vertex_buffer: glium::VertexBuffer<Vertex> = {
-        glium::VertexBuffer::new(
-            &display,
-            reinterpret_cast_slice(&vertices_with_normals, vertices.len()/3)
-        ).expect("failed to create vertex buffer")
-    };
+    let vertex_buffer: glium::VertexBuffer<Vertex> =
+        glium::VertexBuffer::new(&display, &vertices).expect("failed to create vertex buffer");
 
     let index_buffer: glium::IndexBuffer<u32> =
         glium::IndexBuffer::new(&display, PrimitiveType::TrianglesList, &indices)
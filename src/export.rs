@@ -0,0 +1,286 @@
+// Copyright 2017 Tristam MacDonald
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serialises extracted meshes to on-disk formats, so the output of
+//! [`MarchingCubes`](../marching_cubes/struct.MarchingCubes.html) can be inspected or loaded
+//! into other tools without hand-rolling a writer.
+
+use std::io::{self, Write};
+
+use crate::marching_cubes::{Vertex, VertexWithNormal};
+
+/// Writes a Wavefront OBJ mesh, using `vertices` and `indices` as emitted by
+/// [`extract_vertices`](../marching_cubes/struct.MarchingCubes.html#method.extract_vertices)
+/// or [`extract_welded`](../marching_cubes/struct.MarchingCubes.html#method.extract_welded).
+///
+/// `indices` is interpreted as a flat list of triangles, three indices per face.
+pub fn write_obj<W : Write>(writer : &mut W, vertices : &[Vertex], indices : &[u32]) -> io::Result<()> {
+    for vertex in vertices {
+        let p = vertex.position;
+        writeln!(writer, "v {} {} {}", p[0], p[1], p[2])?;
+    }
+
+    write_obj_faces(writer, indices)
+}
+
+/// Writes a Wavefront OBJ mesh including per-vertex normals, as emitted by
+/// [`extract_with_normals`](../marching_cubes/struct.MarchingCubes.html#method.extract_with_normals).
+pub fn write_obj_with_normals<W : Write>(writer : &mut W, vertices : &[VertexWithNormal], indices : &[u32]) -> io::Result<()> {
+    for vertex in vertices {
+        let p = vertex.position;
+        writeln!(writer, "v {} {} {}", p[0], p[1], p[2])?;
+    }
+    for vertex in vertices {
+        let n = vertex.normal;
+        writeln!(writer, "vn {} {} {}", n[0], n[1], n[2])?;
+    }
+
+    write_obj_faces(writer, indices)
+}
+
+fn write_obj_faces<W : Write>(writer : &mut W, indices : &[u32]) -> io::Result<()> {
+    for face in indices.chunks(3) {
+        // OBJ indices are 1-based.
+        writeln!(writer, "f {} {} {}", face[0] + 1, face[1] + 1, face[2] + 1)?;
+    }
+    Ok(())
+}
+
+/// Writes an ASCII PLY mesh, using `vertices` and `indices` as emitted by
+/// [`extract_vertices`](../marching_cubes/struct.MarchingCubes.html#method.extract_vertices)
+/// or [`extract_welded`](../marching_cubes/struct.MarchingCubes.html#method.extract_welded).
+pub fn write_ply<W : Write>(writer : &mut W, vertices : &[Vertex], indices : &[u32]) -> io::Result<()> {
+    write_ply_header(writer, vertices.len(), indices.len() / 3, false, false)?;
+
+    for vertex in vertices {
+        let p = vertex.position;
+        writeln!(writer, "{} {} {}", p[0], p[1], p[2])?;
+    }
+    write_ply_faces_ascii(writer, indices)
+}
+
+/// Writes an ASCII PLY mesh including per-vertex normals, as emitted by
+/// [`extract_with_normals`](../marching_cubes/struct.MarchingCubes.html#method.extract_with_normals).
+pub fn write_ply_with_normals<W : Write>(writer : &mut W, vertices : &[VertexWithNormal], indices : &[u32]) -> io::Result<()> {
+    write_ply_header(writer, vertices.len(), indices.len() / 3, false, true)?;
+
+    for vertex in vertices {
+        let p = vertex.position;
+        let n = vertex.normal;
+        writeln!(writer, "{} {} {} {} {} {}", p[0], p[1], p[2], n[0], n[1], n[2])?;
+    }
+    write_ply_faces_ascii(writer, indices)
+}
+
+fn write_ply_faces_ascii<W : Write>(writer : &mut W, indices : &[u32]) -> io::Result<()> {
+    for face in indices.chunks(3) {
+        writeln!(writer, "3 {} {} {}", face[0], face[1], face[2])?;
+    }
+    Ok(())
+}
+
+/// Writes a binary (little-endian) PLY mesh, using `vertices` and `indices` as emitted by
+/// [`extract_vertices`](../marching_cubes/struct.MarchingCubes.html#method.extract_vertices)
+/// or [`extract_welded`](../marching_cubes/struct.MarchingCubes.html#method.extract_welded).
+pub fn write_ply_binary<W : Write>(writer : &mut W, vertices : &[Vertex], indices : &[u32]) -> io::Result<()> {
+    write_ply_header(writer, vertices.len(), indices.len() / 3, true, false)?;
+
+    for vertex in vertices {
+        for component in vertex.position {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+    }
+    write_ply_faces_binary(writer, indices)
+}
+
+/// Writes a binary (little-endian) PLY mesh including per-vertex normals, as emitted by
+/// [`extract_with_normals`](../marching_cubes/struct.MarchingCubes.html#method.extract_with_normals).
+pub fn write_ply_binary_with_normals<W : Write>(writer : &mut W, vertices : &[VertexWithNormal], indices : &[u32]) -> io::Result<()> {
+    write_ply_header(writer, vertices.len(), indices.len() / 3, true, true)?;
+
+    for vertex in vertices {
+        for component in vertex.position {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+        for component in vertex.normal {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+    }
+    write_ply_faces_binary(writer, indices)
+}
+
+fn write_ply_faces_binary<W : Write>(writer : &mut W, indices : &[u32]) -> io::Result<()> {
+    for face in indices.chunks(3) {
+        writer.write_all(&[3u8])?;
+        for &index in face {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_ply_header<W : Write>(writer : &mut W, vertex_count : usize, face_count : usize, binary : bool, with_normals : bool) -> io::Result<()> {
+    writeln!(writer, "ply")?;
+    if binary {
+        writeln!(writer, "format binary_little_endian 1.0")?;
+    } else {
+        writeln!(writer, "format ascii 1.0")?;
+    }
+    writeln!(writer, "element vertex {}", vertex_count)?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    if with_normals {
+        writeln!(writer, "property float nx")?;
+        writeln!(writer, "property float ny")?;
+        writeln!(writer, "property float nz")?;
+    }
+    writeln!(writer, "element face {}", face_count)?;
+    writeln!(writer, "property list uchar int vertex_indices")?;
+    writeln!(writer, "end_header")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> (Vec<Vertex>, Vec<u32>) {
+        let vertices = vec![
+            Vertex { position: [0.0, 0.0, 0.0] },
+            Vertex { position: [1.0, 0.0, 0.0] },
+            Vertex { position: [0.0, 1.0, 0.0] },
+        ];
+        let indices = vec![0, 1, 2];
+        (vertices, indices)
+    }
+
+    fn triangle_with_normals() -> (Vec<VertexWithNormal>, Vec<u32>) {
+        let vertices = vec![
+            VertexWithNormal { position: [0.0, 0.0, 0.0], normal: [0.0, 0.0, 1.0] },
+            VertexWithNormal { position: [1.0, 0.0, 0.0], normal: [0.0, 0.0, 1.0] },
+            VertexWithNormal { position: [0.0, 1.0, 0.0], normal: [0.0, 0.0, 1.0] },
+        ];
+        let indices = vec![0, 1, 2];
+        (vertices, indices)
+    }
+
+    #[test]
+    fn obj_writes_vertices_and_one_based_faces() {
+        let (vertices, indices) = triangle();
+        let mut out = Vec::new();
+        write_obj(&mut out, &vertices, &indices).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n"
+        );
+    }
+
+    #[test]
+    fn obj_with_normals_writes_positions_then_normals_then_faces() {
+        let (vertices, indices) = triangle_with_normals();
+        let mut out = Vec::new();
+        write_obj_with_normals(&mut out, &vertices, &indices).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             vn 0 0 1\nvn 0 0 1\nvn 0 0 1\n\
+             f 1 2 3\n"
+        );
+    }
+
+    #[test]
+    fn ply_writes_ascii_header_and_body() {
+        let (vertices, indices) = triangle();
+        let mut out = Vec::new();
+        write_ply(&mut out, &vertices, &indices).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ply\n\
+             format ascii 1.0\n\
+             element vertex 3\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             element face 1\n\
+             property list uchar int vertex_indices\n\
+             end_header\n\
+             0 0 0\n1 0 0\n0 1 0\n\
+             3 0 1 2\n"
+        );
+    }
+
+    #[test]
+    fn ply_with_normals_adds_normal_properties_and_columns() {
+        let (vertices, indices) = triangle_with_normals();
+        let mut out = Vec::new();
+        write_ply_with_normals(&mut out, &vertices, &indices).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ply\n\
+             format ascii 1.0\n\
+             element vertex 3\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             property float nx\n\
+             property float ny\n\
+             property float nz\n\
+             element face 1\n\
+             property list uchar int vertex_indices\n\
+             end_header\n\
+             0 0 0 0 0 1\n1 0 0 0 0 1\n0 1 0 0 0 1\n\
+             3 0 1 2\n"
+        );
+    }
+
+    #[test]
+    fn ply_binary_matches_little_endian_byte_layout() {
+        let (vertices, indices) = triangle();
+        let mut out = Vec::new();
+        write_ply_binary(&mut out, &vertices, &indices).unwrap();
+
+        let header_end = out.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        let header = String::from_utf8(out[..header_end].to_vec()).unwrap();
+        assert_eq!(
+            header,
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             element vertex 3\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             element face 1\n\
+             property list uchar int vertex_indices\n\
+             end_header\n"
+        );
+
+        let mut expected_body = Vec::new();
+        for vertex in &vertices {
+            for component in vertex.position {
+                expected_body.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        expected_body.push(3u8);
+        for &index in &indices {
+            expected_body.extend_from_slice(&index.to_le_bytes());
+        }
+
+        assert_eq!(&out[header_end..], &expected_body[..]);
+    }
+}
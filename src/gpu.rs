@@ -0,0 +1,266 @@
+// Copyright 2017 Tristam MacDonald
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runs marching cubes entirely on the GPU via a compute shader, so that large or
+//! high-resolution volumes can be extracted without a CPU round trip.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::marching_cubes::MarchingCubes;
+use crate::marching_cubes_tables::{EDGE_CONNECTION, TRIANGLE_CONNECTION};
+
+/// A source capable of yielding a GPU-evaluatable expression for a signed distance field.
+///
+/// Unlike [`Source`](../marching_cubes/trait.Source.html), which is sampled from Rust, a
+/// `GpuSource` hands back a snippet of shader code that is spliced into the extraction
+/// compute shader, so the whole extraction - field evaluation included - runs on the GPU.
+pub trait GpuSource {
+    /// Returns a WGSL expression, in terms of the in-scope `f32` variables `x`, `y` and `z`
+    /// (each a coordinate in the unit cube), that evaluates this source's signed distance
+    /// at that point.
+    fn wgsl_expression(&self) -> String;
+}
+
+/// The vertex/index buffers produced by a GPU marching cubes extraction.
+///
+/// Both buffers are allocated for the worst case (every voxel emitting five triangles) so
+/// that they can be bound directly as a vertex/index buffer pair without a CPU-side resize;
+/// only the first `vertex_count`/`index_count` entries of each are meaningful.
+pub struct GpuMesh {
+    pub vertex_buffer : wgpu::Buffer,
+    pub index_buffer : wgpu::Buffer,
+    pub vertex_count : u32,
+    pub index_count : u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct GpuVertex {
+    position : [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Params {
+    size : u32,
+    one_over_size : f32,
+    _padding : [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Counters {
+    vertex_count : u32,
+    index_count : u32,
+}
+
+const SHADER_TEMPLATE : &str = include_str!("gpu_marching_cubes.wgsl");
+
+/// Returned by [`extract_gpu`](../marching_cubes/struct.MarchingCubes.html#method.extract_gpu)
+/// when the requested chunk `size`'s worst-case vertex or index buffer would exceed the
+/// device's storage buffer limits.
+#[derive(Debug)]
+pub struct GpuExtractionError {
+    pub requested_bytes : u64,
+    pub limit_bytes : u64,
+}
+
+impl std::fmt::Display for GpuExtractionError {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "marching cubes GPU extraction needs a {} byte buffer, which exceeds this device's \
+             {} byte storage buffer limit; use a smaller chunk size",
+            self.requested_bytes, self.limit_bytes
+        )
+    }
+}
+
+impl std::error::Error for GpuExtractionError {}
+
+fn checked_buffer_size(requested_bytes : u64, device : &wgpu::Device) -> Result<u64, GpuExtractionError> {
+    let limits = device.limits();
+    let limit_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    if requested_bytes > limit_bytes {
+        return Err(GpuExtractionError { requested_bytes, limit_bytes });
+    }
+    Ok(requested_bytes)
+}
+
+impl MarchingCubes {
+    /// Extracts a mesh from the given [`GpuSource`](trait.GpuSource.html) using a compute
+    /// shader, dispatching one invocation per voxel and writing the resulting vertices and
+    /// indices directly into GPU storage buffers.
+    ///
+    /// `size` is the chunk resolution, matching the value passed to
+    /// [`MarchingCubes::new`](../marching_cubes/struct.MarchingCubes.html#method.new). The
+    /// returned [`GpuMesh`](struct.GpuMesh.html) can be bound straight into a render pass,
+    /// with no CPU readback required.
+    ///
+    /// Both output buffers are sized for the worst case (every voxel emitting five
+    /// triangles), so a large enough `size` can ask for more storage than `device` allows;
+    /// this is checked against [`wgpu::Limits`](https://docs.rs/wgpu) up front and reported
+    /// as [`GpuExtractionError`](struct.GpuExtractionError.html) rather than left to panic
+    /// inside `create_buffer`.
+    pub fn extract_gpu<S : GpuSource>(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        size : usize,
+        source : &S,
+    ) -> Result<GpuMesh, GpuExtractionError> {
+        let size_minus_one = (size - 1) as u32;
+        let max_triangles = size_minus_one as u64 * size_minus_one as u64 * size_minus_one as u64 * 5;
+        let max_vertices = max_triangles * 3;
+        let max_indices = max_triangles * 3;
+
+        let vertex_buffer_size = checked_buffer_size(max_vertices * std::mem::size_of::<GpuVertex>() as u64, device)?;
+        let index_buffer_size = checked_buffer_size(max_indices * std::mem::size_of::<u32>() as u64, device)?;
+
+        let shader_source = SHADER_TEMPLATE.replace("{{SAMPLE}}", &source.wgsl_expression());
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("marching cubes extraction"),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        let params = Params {
+            size: size as u32,
+            one_over_size: 1.0 / size_minus_one as f32,
+            _padding: [0; 2],
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("marching cubes params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let edge_connection : Vec<[u32; 2]> = EDGE_CONNECTION
+            .iter()
+            .map(|edge| [edge[0] as u32, edge[1] as u32])
+            .collect();
+        let edge_connection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("marching cubes edge table"),
+            contents: bytemuck::cast_slice(&edge_connection),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let triangle_connection : Vec<i32> = TRIANGLE_CONNECTION
+            .iter()
+            .flat_map(|row| row.iter().map(|&v| v as i32))
+            .collect();
+        let triangle_connection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("marching cubes triangle table"),
+            contents: bytemuck::cast_slice(&triangle_connection),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let counters_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("marching cubes counters"),
+            contents: bytemuck::bytes_of(&Counters { vertex_count: 0, index_count: 0 }),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching cubes vertices"),
+            size: vertex_buffer_size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching cubes indices"),
+            size: index_buffer_size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDEX
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("marching cubes extraction"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("marching cubes extraction"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: edge_connection_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: triangle_connection_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: counters_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: vertex_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: index_buffer.as_entire_binding() },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("marching cubes extraction"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("marching cubes extraction"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(size_minus_one, size_minus_one, size_minus_one);
+        }
+
+        let counters_readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching cubes counters readback"),
+            size: std::mem::size_of::<Counters>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(
+            &counters_buffer,
+            0,
+            &counters_readback,
+            0,
+            std::mem::size_of::<Counters>() as u64,
+        );
+
+        queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        counters_readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("counters readback channel closed")
+            .expect("failed to map counters readback buffer");
+
+        let counters : Counters = {
+            let view = counters_readback.slice(..).get_mapped_range();
+            *bytemuck::from_bytes(&view)
+        };
+        counters_readback.unmap();
+
+        Ok(GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            vertex_count: counters.vertex_count,
+            index_count: counters.index_count,
+        })
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2017 Tristam MacDonald
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Algorithms for extracting triangle meshes from isosurfaces.
+
+/// A highly-optimised marching cubes implementation.
+pub mod marching_cubes;
+
+/// GPU compute-shader marching cubes extraction. Requires the `gpu` feature.
+#[cfg(feature = "gpu")]
+pub mod gpu;
+
+/// Serialises extracted meshes to OBJ and PLY files.
+pub mod export;
+
+mod marching_cubes_tables;